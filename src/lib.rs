@@ -23,6 +23,52 @@
 //!
 //! It is an error if the given directory contains no source files.
 //!
+//! Entries matched by a `.gitignore` found under the scanned directory are
+//! skipped, along with `.git`, `target`, and `node_modules` which are always
+//! skipped even without a `.gitignore` rule for them. Name a directory
+//! explicitly as the argument to `dir!` to scan it anyway.
+//!
+//! Individual files or subtrees can be excluded with a `skip` argument,
+//! naming paths relative to the scanned directory:
+//!
+//! ```
+//! # const IGNORE: &str = stringify! {
+//! automod::dir!("tests/regression", skip = ["issue_wip.rs", "scratch"]);
+//! # };
+//! ```
+//!
+//! Prefixing the path with `reexport` additionally emits a `use #name::*;`
+//! next to each `mod #name;`, for directories meant to be glob-imported
+//! wholesale:
+//!
+//! ```
+//! # const IGNORE: &str = stringify! {
+//! automod::dir!(reexport "src/plugins");
+//! # };
+//! ```
+//!
+//! A subdirectory with no `mod.rs` of its own is mirrored as a nested `mod`
+//! item rather than flattened into the parent with underscores, so:
+//!
+//! - src/
+//!   - plugins/
+//!     - a.rs
+//!     - nested/
+//!       - b.rs
+//!
+//! expands to:
+//!
+//! ```
+//! # const IGNORE: &str = stringify! {
+//! mod plugins {
+//!     mod a;
+//!     mod nested {
+//!         mod b;
+//!     }
+//! }
+//! # };
+//! ```
+//!
 //! # Example
 //!
 //! Suppose that we would like to keep a directory of regression tests for
@@ -62,26 +108,84 @@
 
 extern crate proc_macro;
 
+mod error;
+
+use error::{Error, Result};
+use ignore::gitignore::Gitignore;
 use proc_macro::TokenStream;
-use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::quote;
 use std::env;
 use std::ffi::OsStr;
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
 use syn::parse::{Parse, ParseStream};
-use syn::{parse_macro_input, LitStr, Visibility};
+use syn::{bracketed, parse_macro_input, Ident, LitStr, Token, Visibility};
+
+// Always skipped, even when there is no .gitignore rule for them. Name one of
+// these directories explicitly as the argument to `dir!` to scan it anyway.
+const DEFAULT_SKIP: &[&str] = &[".git", "target", "node_modules"];
+
+// A source file, or a subdirectory without its own mod.rs, mirrored as a
+// nested mod item.
+enum Module {
+    File { ident: String, path: String },
+    Dir { ident: String, children: Vec<Module> },
+}
+
+impl Module {
+    fn ident(&self) -> &str {
+        match self {
+            Module::File { ident, .. } | Module::Dir { ident, .. } => ident,
+        }
+    }
+}
 
 struct Arg {
     vis: Visibility,
+    reexport: bool,
     path: LitStr,
+    skip: Vec<PathBuf>,
 }
 
 impl Parse for Arg {
     fn parse(input: ParseStream) -> syn::Result<Self> {
+        let vis = input.parse()?;
+
+        let reexport = if input.peek(Ident) {
+            let kw: Ident = input.parse()?;
+            if kw != "reexport" {
+                return Err(syn::Error::new_spanned(kw, "expected `reexport`"));
+            }
+            true
+        } else {
+            false
+        };
+
+        let path = input.parse()?;
+
+        let mut skip = Vec::new();
+        if input.parse::<Option<Token![,]>>()?.is_some() {
+            let kw: Ident = input.parse()?;
+            if kw != "skip" {
+                return Err(syn::Error::new_spanned(kw, "expected `skip`"));
+            }
+            input.parse::<Token![=]>()?;
+            let content;
+            bracketed!(content in input);
+            let paths = content.parse_terminated(<LitStr as Parse>::parse, Token![,])?;
+            skip = paths
+                .iter()
+                .map(|lit| PathBuf::from(lit.value().trim_end_matches('/')))
+                .collect();
+        }
+
         Ok(Arg {
-            vis: input.parse()?,
-            path: input.parse()?,
+            vis,
+            reexport,
+            path,
+            skip,
         })
     }
 }
@@ -97,62 +201,203 @@ pub fn dir(input: TokenStream) -> TokenStream {
         None => PathBuf::from(rel_path),
     };
 
-    let expanded = source_files(&dir, &dir)
-        .into_iter()
-        .map(|(path, name)| {
-            let ident = Ident::new(&name.replace('-', "_"), Span::call_site());
-            quote! {
-                #[path = #path]
-                #vis mod #ident;
-            }
-        })
-        .collect::<TokenStream2>();
+    let modules = match source_files(&dir, &dir, &mut Vec::new(), &input.skip) {
+        Ok(modules) if !modules.is_empty() => modules,
+        Ok(_) => return to_compile_error(Error::Empty, &input.path),
+        Err(err) => return to_compile_error(err, &input.path),
+    };
+    let expanded = render_modules(&modules, vis, input.reexport);
 
     //println!("{expanded}");
 
     TokenStream::from(expanded)
 }
 
-fn source_files(top_dir: &Path, current_dir: &Path) -> Vec<(String, String)> {
-    let mut paths = Vec::new();
+fn to_compile_error(err: Error, path: &LitStr) -> TokenStream {
+    TokenStream::from(syn::Error::new(path.span(), err).to_compile_error())
+}
+
+fn non_utf8_path_error(path: &Path) -> Error {
+    Error::Io(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("path is not valid UTF-8: {}", path.display()),
+    ))
+}
+
+fn io_path_error(path: &Path, err: io::Error) -> Error {
+    Error::Io(io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))
+}
+
+fn render_modules(modules: &[Module], vis: &Visibility, reexport: bool) -> TokenStream2 {
+    modules
+        .iter()
+        .map(|module| render_module(module, vis, reexport))
+        .collect()
+}
+
+fn render_module(module: &Module, vis: &Visibility, reexport: bool) -> TokenStream2 {
+    let ident = Ident::new(module.ident(), Span::call_site());
+    match module {
+        Module::File { path, .. } => {
+            if reexport {
+                quote! {
+                    #[path = #path]
+                    #vis mod #ident;
+                    #vis use #ident::*;
+                }
+            } else {
+                quote! {
+                    #[path = #path]
+                    #vis mod #ident;
+                }
+            }
+        }
+        Module::Dir { children, .. } => {
+            let inner = render_modules(children, vis, reexport);
+            if reexport {
+                quote! {
+                    #vis mod #ident {
+                        #inner
+                    }
+                    #vis use #ident::*;
+                }
+            } else {
+                quote! {
+                    #vis mod #ident {
+                        #inner
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn source_files(
+    top_dir: &Path,
+    current_dir: &Path,
+    ignores: &mut Vec<Gitignore>,
+    skip: &[PathBuf],
+) -> Result<Vec<Module>> {
+    let mut modules = Vec::new();
+
+    let gitignore_path = current_dir.join(".gitignore");
+    let pushed_ignore = if gitignore_path.is_file() {
+        let (gitignore, err) = Gitignore::new(&gitignore_path);
+        if let Some(err) = err {
+            let err = io::Error::new(io::ErrorKind::InvalidData, err);
+            return Err(io_path_error(&gitignore_path, err));
+        }
+        ignores.push(gitignore);
+        true
+    } else {
+        false
+    };
 
-    for entry in fs::read_dir(current_dir).unwrap() {
-        let entry = entry.unwrap();
+    let read_dir = fs::read_dir(current_dir).map_err(|err| io_path_error(current_dir, err))?;
+    for entry in read_dir {
+        let entry = entry.map_err(|err| io_path_error(current_dir, err))?;
         let path = entry.path();
-        let name_path = path
-            .canonicalize()
-            .unwrap()
-            .strip_prefix(Path::new(top_dir).canonicalize().unwrap())
-            .unwrap()
-            .with_extension("");
-        let name = name_path
-            .components()
-            .map(|x| match x {
-                std::path::Component::Normal(x) => x.to_str().unwrap(),
-                _ => panic!(),
-            })
-            .collect::<Vec<_>>()
-            .join("_");
-
-        if entry.file_type().unwrap().is_dir() {
+        let is_dir = entry
+            .file_type()
+            .map_err(|err| io_path_error(&path, err))?
+            .is_dir();
+
+        if DEFAULT_SKIP
+            .iter()
+            .any(|skip| entry.file_name() == OsStr::new(skip))
+        {
+            continue;
+        }
+
+        // The most specific (deepest) gitignore that actually matches wins,
+        // the same way git itself resolves a negated pattern in a nested
+        // .gitignore overriding a broader rule from a parent .gitignore.
+        let ignored = ignores.iter().rev().find_map(|gitignore| {
+            match gitignore.matched(&path, is_dir) {
+                ignore::Match::None => None,
+                m => Some(m.is_ignore()),
+            }
+        });
+        if ignored.unwrap_or(false) {
+            continue;
+        }
+
+        let rel_path = path.strip_prefix(top_dir).unwrap();
+        if skip.iter().any(|skip| rel_path == skip) {
+            continue;
+        }
+
+        let ident = path
+            .file_stem()
+            .and_then(OsStr::to_str)
+            .ok_or_else(|| non_utf8_path_error(&path))?
+            .replace('-', "_");
+
+        if is_dir {
             let mod_file = path.join("mod.rs");
-            if mod_file.exists() && mod_file.is_file() {
-                paths.push((mod_file.into_os_string().into_string().unwrap(), name));
+            if mod_file.is_file() {
+                let mod_file_path = mod_file
+                    .clone()
+                    .into_os_string()
+                    .into_string()
+                    .map_err(|_| non_utf8_path_error(&mod_file))?;
+                modules.push(Module::File {
+                    ident,
+                    path: mod_file_path,
+                });
             } else {
-                paths.append(&mut source_files(top_dir, &path));
+                let children = source_files(top_dir, &path, ignores, skip)?;
+                if !children.is_empty() {
+                    modules.push(Module::Dir { ident, children });
+                }
             }
-        } else if entry.file_type().unwrap().is_file() {
+        } else if entry
+            .file_type()
+            .map_err(|err| io_path_error(&path, err))?
+            .is_file()
+        {
             let file_name = path.file_name().unwrap();
             if file_name == "mod.rs" || file_name == "lib.rs" || file_name == "main.rs" {
                 continue;
             }
 
             if path.extension() == Some(OsStr::new("rs")) {
-                paths.push((path.into_os_string().into_string().unwrap(), name));
+                let file_path = path
+                    .clone()
+                    .into_os_string()
+                    .into_string()
+                    .map_err(|_| non_utf8_path_error(&path))?;
+                modules.push(Module::File {
+                    ident,
+                    path: file_path,
+                });
             }
         }
     }
 
-    paths.sort();
-    return paths;
+    if pushed_ignore {
+        ignores.pop();
+    }
+
+    modules.sort_by(|a, b| a.ident().cmp(b.ident()));
+    Ok(modules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_path_error_includes_the_offending_path() {
+        let err = io::Error::new(io::ErrorKind::NotFound, "No such file or directory (os error 2)");
+        let message = io_path_error(Path::new("tests/does-not-exist"), err).to_string();
+        assert!(message.contains("tests/does-not-exist"));
+        assert!(message.contains("No such file or directory"));
+    }
+
+    #[test]
+    fn non_utf8_path_error_includes_the_offending_path() {
+        let message = non_utf8_path_error(Path::new("tests/bad")).to_string();
+        assert!(message.contains("tests/bad"));
+    }
 }
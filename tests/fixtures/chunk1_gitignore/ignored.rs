@@ -0,0 +1 @@
+this is not valid rust and must never be compiled in
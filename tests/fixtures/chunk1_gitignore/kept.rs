@@ -0,0 +1 @@
+pub const VALUE: i32 = 1;
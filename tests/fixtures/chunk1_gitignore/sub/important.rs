@@ -0,0 +1 @@
+pub const IMPORTANT: i32 = 42;
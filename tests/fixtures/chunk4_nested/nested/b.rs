@@ -0,0 +1 @@
+pub const B: i32 = 2;
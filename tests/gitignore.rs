@@ -0,0 +1,21 @@
+// ignored.rs and sub/other.rs contain invalid Rust and are excluded by
+// .gitignore rules; if source_files ever stopped respecting them, or
+// resolved nested .gitignore precedence wrong, this test crate would fail
+// to compile rather than silently passing.
+//
+// sub/.gitignore negates the parent's `sub/*.rs` rule for important.rs
+// specifically, covering the case where the more specific (deeper)
+// .gitignore must win over a broader rule from an ancestor.
+mod fixture {
+    automod::dir!(pub "tests/fixtures/chunk1_gitignore");
+}
+
+#[test]
+fn gitignored_files_are_skipped() {
+    assert_eq!(fixture::kept::VALUE, 1);
+}
+
+#[test]
+fn nested_gitignore_negation_overrides_parent_rule() {
+    assert_eq!(fixture::sub::important::IMPORTANT, 42);
+}
@@ -0,0 +1,11 @@
+// nested/ has no mod.rs of its own, so it must expand to a genuinely
+// nested `mod nested { mod b; }` rather than a flattened `mod nested_b`.
+mod fixture {
+    automod::dir!(pub "tests/fixtures/chunk4_nested");
+}
+
+#[test]
+fn subdirectories_are_mirrored_as_nested_modules() {
+    assert_eq!(fixture::a::A, 1);
+    assert_eq!(fixture::nested::b::B, 2);
+}
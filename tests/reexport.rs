@@ -0,0 +1,11 @@
+// `reexport` mode must emit `use plugin_a::*;` alongside `mod plugin_a;`,
+// so NAME is reachable directly off `fixture` without naming the file.
+mod fixture {
+    automod::dir!(pub reexport "tests/fixtures/chunk3_reexport");
+}
+
+#[test]
+fn reexport_emits_use_alongside_mod() {
+    assert_eq!(fixture::NAME, "a");
+    assert_eq!(fixture::plugin_a::NAME, "a");
+}
@@ -0,0 +1,11 @@
+// scratch.rs and the wip/ subtree both contain invalid Rust; the skip
+// argument prunes the individual file and the whole subtree before either
+// is turned into a mod item.
+mod fixture {
+    automod::dir!(pub "tests/fixtures/chunk2_skip", skip = ["scratch.rs", "wip"]);
+}
+
+#[test]
+fn skip_argument_excludes_named_file_and_subtree() {
+    assert_eq!(fixture::keep::VALUE, 2);
+}